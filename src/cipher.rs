@@ -0,0 +1,13 @@
+/// A symmetric stream cipher that can be seeded from a key and then process (encrypt or
+/// decrypt) data in place.
+///
+/// `CustomCompressor` and `CustomTransformer` are generic over this trait so that firmware
+/// variants that swap RC4 for another stream cipher can reuse the same compressor/transformer
+/// plumbing.
+pub trait StreamCipher: Copy {
+    /// Create a new instance of the cipher, seeded with `key`.
+    fn new(key: &[u8]) -> Self;
+
+    /// Encrypt or decrypt `data` in place.
+    fn process(&mut self, data: &mut [u8]);
+}
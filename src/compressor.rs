@@ -4,50 +4,73 @@ use backhand::{
     compression::DefaultCompressor, kind::Kind,
 };
 
-use crate::rc4::RC4;
+use crate::cipher::StreamCipher;
 
 #[derive(Copy, Clone)]
-pub struct CustomCompressor {
+pub struct CustomCompressor<C: StreamCipher> {
     key: [u8; 16],
+    /// Data block size from the superblock, used to size the buffer for formats (like lz4)
+    /// that don't self-describe their decompressed length.
+    block_size: u32,
+    _cipher: std::marker::PhantomData<C>,
 }
 
-impl CustomCompressor {
+impl<C: StreamCipher> CustomCompressor<C> {
     // Compressors need a static lifetime, so we need to leak the box
-    pub fn new_static(key: [u8; 16]) -> &'static Self {
-        let compressor = Box::new(Self { key });
+    pub fn new_static(key: [u8; 16], block_size: u32) -> &'static Self {
+        let compressor = Box::new(Self { key, block_size, _cipher: std::marker::PhantomData });
         Box::leak(compressor)
     }
 }
 
-// Special decompress that only has support for the Rust version of gzip: zune-inflate for
-// decompression.
-impl CompressionAction for CustomCompressor {
+impl<C: StreamCipher + 'static> CompressionAction for CustomCompressor<C> {
     fn decompress(
         &self,
         bytes: &[u8],
         out: &mut Vec<u8>,
-        _: Compressor,
+        compressor: Compressor,
     ) -> Result<(), BackhandError> {
         // Clone the bytes to a buffer
         let mut buffer = bytes.to_vec();
 
-        // Decrypt the bytes using RC4
-        let mut rc4 = RC4::new(&self.key);
-        rc4.process(&mut buffer);
+        // Decrypt the bytes using the configured stream cipher
+        let mut cipher = C::new(&self.key);
+        cipher.process(&mut buffer);
 
-        // Decompress the bytes using Gzip
-        DefaultCompressor.decompress(&buffer, out, Compressor::Gzip)?;
+        // Honor whichever compressor `backhand` actually resolved this block against (read from
+        // the superblock/compression-options metadata) instead of assuming one ourselves - the
+        // probe `CustomCompressor` built in `build_kind` doesn't know the real compressor yet.
+        //
+        // lz4 isn't handled by DefaultCompressor, so decompress it with a pure-Rust
+        // implementation to keep the "no C deps" property for the common Rockchip lz4 case.
+        if compressor == Compressor::Lz4 {
+            let decompressed = lz4_flex::block::decompress(&buffer, self.block_size as usize)
+                .map_err(|_| BackhandError::CorruptedOrInvalidSquashfs)?;
+            out.extend_from_slice(&decompressed);
+            return Ok(());
+        }
+
+        DefaultCompressor.decompress(&buffer, out, compressor)?;
         Ok(())
     }
 
-    // Just pass to default compressor
+    // Compress with whichever compressor `fc` actually carries - `FilesystemWriter::from_fs_reader`
+    // copies the original image's resolved compressor into it, so this keeps the data blocks
+    // consistent with the compressor id the new superblock claims - then encrypt with a fresh
+    // keystream, mirroring the per-block `C::new(&key)` that `decompress` assumes when it
+    // decrypts a block before decompressing it.
     fn compress(
         &self,
         bytes: &[u8],
         fc: FilesystemCompressor,
         block_size: u32,
     ) -> Result<Vec<u8>, BackhandError> {
-        DefaultCompressor.compress(bytes, fc, block_size)
+        let mut compressed = DefaultCompressor.compress(bytes, fc, block_size)?;
+
+        let mut cipher = C::new(&self.key);
+        cipher.process(&mut compressed);
+
+        Ok(compressed)
     }
 
     // pass the default options
@@ -0,0 +1,82 @@
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// Build the standard reflected CRC-32 lookup table for [`POLYNOMIAL`].
+fn build_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ POLYNOMIAL } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Incremental reflected CRC-32 (the "CRC-32/ISO-HDLC" variant used by zip, gzip, and the RKAF
+/// container trailer): polynomial `0xEDB88320`, initial value `0xFFFFFFFF`, final XOR
+/// `0xFFFFFFFF`.
+pub struct Crc32 {
+    table: [u32; 256],
+    value: u32,
+}
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Self { table: build_table(), value: 0xFFFF_FFFF }
+    }
+
+    /// Feed more bytes into the running checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            let index = ((self.value ^ byte as u32) & 0xff) as usize;
+            self.value = (self.value >> 8) ^ self.table[index];
+        }
+    }
+
+    /// Consume the hasher and return the final checksum.
+    pub fn finalize(self) -> u32 {
+        self.value ^ 0xFFFF_FFFF
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_vector() {
+        // Standard CRC-32 check value for the ASCII string "123456789"
+        let mut crc = Crc32::new();
+        crc.update(b"123456789");
+        assert_eq!(crc.finalize(), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_incremental_matches_single_call() {
+        let mut whole = Crc32::new();
+        whole.update(b"Hello, world!");
+
+        let mut chunked = Crc32::new();
+        chunked.update(b"Hello, ");
+        chunked.update(b"world!");
+
+        assert_eq!(whole.finalize(), chunked.finalize());
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let crc = Crc32::new();
+        assert_eq!(crc.finalize(), 0);
+    }
+}
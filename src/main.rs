@@ -1,21 +1,49 @@
 use backhand::kind::Kind;
-use backhand::{BufReadSeek, FilesystemReader, FilesystemWriter, Squashfs};
-use clap::Parser;
+use backhand::{BufReadSeek, FilesystemReader, FilesystemWriter, InnerNode, Squashfs};
+use clap::{Parser, ValueEnum};
 use std::fs::File;
-use std::io::{Cursor, Read};
-use std::path::Path;
+use std::io::{BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::process;
 
+mod aes128_ctr;
+mod cipher;
 mod compressor;
+mod crc32;
 mod rc4;
+mod rkaf;
+mod squashfs;
+mod transformer;
+mod verify;
 
+use aes128_ctr::Aes128Ctr;
+use cipher::StreamCipher;
 use compressor::CustomCompressor;
 use rc4::RC4;
+use rkaf::RkafContainer;
+use squashfs::SQUASHFS_METADATA_SIZE;
+use transformer::HeaderDecryptingReader;
+use verify::VerifyResult;
+
+/// Stream cipher used by the firmware to encrypt the inner SquashFS image.
+#[derive(Copy, Clone, ValueEnum)]
+enum Cipher {
+    Rc4,
+    Aes128Ctr,
+}
 
 /// Convert a hex string to bytes
 fn hex_to_bytes(hex: &str) -> Result<[u8; 16], &'static str> {
     let hex = hex.replace(" ", "");
 
+    // Reject non-ASCII input before slicing by byte index below: a multi-byte UTF-8 character
+    // can make `hex.len()` (a byte count) equal 32 without `hex` actually being 32 hex digits,
+    // which would otherwise panic on a non-char-boundary slice instead of being reported as an
+    // invalid candidate.
+    if !hex.is_ascii() {
+        return Err("Hex string must contain only ASCII hex digits");
+    }
+
     // Check if the hex string has 32 characters
     if hex.len() != 32 {
         return Err("Hex string must be 32 characters long");
@@ -37,88 +65,104 @@ fn hex_to_bytes(hex: &str) -> Result<[u8; 16], &'static str> {
 
 #[derive(Parser)]
 #[command(name = "SquashFS Decryptor")]
-#[command(about = "Decrypts SquashFS files using RC4")]
+#[command(about = "Decrypts SquashFS files encrypted with RC4 or AES-128-CTR")]
 struct Cli {
     input_file: String,
+    /// Hex-encoded 16-byte key. Either this or --keyfile must be given.
+    #[arg(long)]
+    key: Option<String>,
+    /// Extract a single file from the image instead of rewriting the whole filesystem.
+    /// Only the blocks belonging to this file are decrypted and decompressed.
     #[arg(long)]
-    key: String,
+    extract: Option<PathBuf>,
+    /// Re-encrypt instead of decrypt: compress each block with the image's own compressor and
+    /// encrypt it, and re-encrypt
+    /// the header on the final image, producing a file the original loader accepts.
+    #[arg(long)]
+    repack: bool,
+    /// Stream cipher the firmware uses to encrypt the image.
+    #[arg(long, value_enum, default_value = "rc4")]
+    cipher: Cipher,
+    /// Treat the input as an RKAF/update.img container and operate on the named partition's
+    /// SquashFS image instead of the whole file. The container's trailing CRC-32 is verified
+    /// before the partition is located.
+    #[arg(long)]
+    partition: Option<String>,
+    /// Run the known-plaintext key check against the superblock and exit, without extracting
+    /// or repacking anything.
+    #[arg(long)]
+    check: bool,
+    /// Newline-separated file of candidate hex keys. Each is tried against the
+    /// known-plaintext check and the first that validates is used instead of --key.
+    #[arg(long)]
+    keyfile: Option<PathBuf>,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
     let input_file = &cli.input_file;
-    let key_str = &cli.key;
 
-    // Convert key from hex string to bytes
-    let key = match hex_to_bytes(key_str) {
+    // Check if input file exists
+    if !Path::new(input_file).exists() {
+        println!("Error: Input file '{}' not found", input_file);
+        process::exit(1);
+    }
+
+    // If asked for a partition, treat the input as an RKAF container: verify its trailing
+    // CRC-32 up front and resolve the partition's offset before touching the inner SquashFS.
+    let offset = match &cli.partition {
+        Some(partition_name) => find_partition_offset(input_file, partition_name)?,
+        None => 0,
+    };
+
+    // Resolve the key, either directly from --key or by scanning --keyfile for the first
+    // candidate that passes the known-plaintext check below.
+    let key = match resolve_key(&cli, input_file, offset) {
         Ok(k) => k,
         Err(e) => {
-            println!("Error parsing key: {}", e);
+            println!("Error: {}", e);
             process::exit(1);
         }
     };
 
-    println!("Using key: {}", key_str);
-
-    // Check if input file exists
-    if !Path::new(input_file).exists() {
-        println!("Error: Input file '{}' not found", input_file);
+    // Decrypt just the start of the superblock and check it against what a valid image must
+    // contain, so a wrong key fails fast here instead of producing an opaque error deep
+    // inside `backhand`.
+    let verification = verify_for_cipher(cli.cipher, key, input_file, offset)?;
+    print_verification(&verification);
+    if !verification.is_valid() {
+        println!("Error: key failed verification against the SquashFS superblock");
         process::exit(1);
     }
 
-    // Open the input file
-    let mut file = File::open(input_file)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
-
-    // Decrypt the header data using RC4, as this won't be compressed
-    let mut rc4 = RC4::new(&key);
-    rc4.process(&mut buffer[..96]);
-
-    // Create a cursor for the superblock
-    let cursor = Cursor::new(buffer.clone());
-    let mut reader: Box<dyn BufReadSeek> = Box::new(cursor);
-    let superblock = match Squashfs::superblock_and_compression_options(
-        &mut reader,
-        &Kind::from_target("le_v4_0")?,
-    )? {
-        (superblock, _) => superblock,
+    if cli.check {
+        println!("Key verified successfully");
+        return Ok(());
+    }
+
+    // Build a Kind that decrypts compressed blocks per block as they're read, and a reader
+    // that decrypts the raw header region in front of it. Together they let `FilesystemReader`
+    // seek straight to the region it needs and decrypt only that region, so we never have to
+    // slurp the whole image into memory or decrypt it up front.
+    let (kind, reader) = match cli.cipher {
+        Cipher::Rc4 => build_kind::<RC4>(key, input_file, offset)?,
+        Cipher::Aes128Ctr => build_kind::<Aes128Ctr>(key, input_file, offset)?,
     };
 
-    // Find and decrypt the fragment table size, ready for decryption and decompression
-    // TODO: Check if the fragment table is present & how many blocks it has
-    // This implementation assumes that the fragment table is present and has 1 block
-    let fragment_table_lookup_ptr = superblock.frag_table as usize;
-    let mut rc4 = RC4::new(&key);
-    rc4.process(&mut buffer[fragment_table_lookup_ptr..fragment_table_lookup_ptr + 8]);
-    let fragment_table_ptr = usize::from_le_bytes(
-        buffer[fragment_table_lookup_ptr..fragment_table_lookup_ptr + 8].try_into()?,
-    );
-    // Decrypt the u16 size of the start of the fragment table, without reseting the RC4 state
-    rc4.process(&mut buffer[fragment_table_ptr..fragment_table_ptr + 2]);
-
-    // Find and decrypt the lookup table size, ready for decryption and decompression
-    let export_table_lookup_ptr = superblock.export_table as usize;
-    let mut rc4 = RC4::new(&key);
-    rc4.process(&mut buffer[export_table_lookup_ptr..export_table_lookup_ptr + 8]);
-    let export_table_ptr = usize::from_le_bytes(
-        buffer[export_table_lookup_ptr..export_table_lookup_ptr + 8].try_into()?,
-    );
-    // Decrypt the u16 size of the export table, without reseting the RC4 state
-    rc4.process(&mut buffer[export_table_ptr..export_table_ptr + 2]);
+    let filesystem_reader =
+        FilesystemReader::from_reader_with_offset_and_kind(reader, offset, kind)?;
 
-    // Find and decrypt the ID table size, ready for decryption and decompression
-    let id_table_lookup_ptr = superblock.id_table as usize;
-    let mut rc4 = RC4::new(&key);
-    rc4.process(&mut buffer[id_table_lookup_ptr..id_table_lookup_ptr + 8]);
+    if let Some(extract) = &cli.extract {
+        return extract_file(&filesystem_reader, extract);
+    }
 
-    // Create the custom compressor with the key.
-    // This needs to be a static reference, so we use the new_static function.
-    let compressor = CustomCompressor::new_static(key);
-    let kind = Kind::new(compressor);
-    let cursor = Cursor::new(buffer);
-    let filesystem_reader = FilesystemReader::from_reader_with_offset_and_kind(cursor, 0, kind)?;
+    if cli.repack {
+        return match cli.cipher {
+            Cipher::Rc4 => repack_image::<RC4>(&filesystem_reader, key),
+            Cipher::Aes128Ctr => repack_image::<Aes128Ctr>(&filesystem_reader, key),
+        };
+    }
 
     filesystem_reader.files().for_each(|file| {
         println!("File: {}", file.fullpath.display());
@@ -134,3 +178,203 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Build a `Kind` that decrypts+decompresses each SquashFS block through `C`, plus the
+/// decrypting reader to pair it with. Two passes are needed: first decrypt just the superblock
+/// so we know the image's block size (which bounds lz4 decompression) and where the
+/// fragment/export/ID tables the superblock points to actually live; then resolve those
+/// scattered table regions too (offsets `backhand`'s writer only fixes up after it has written
+/// the data/inode/dir tables, so they can't be known before the superblock is read) and build
+/// the real reader around everything we found. The compressor id read in the first pass is not
+/// kept: `CustomCompressor` decompresses each block with whichever compressor `backhand` passes
+/// in for it. A metadata-sized buffer is enough for the probe, since compression options (if
+/// present) fit in one metadata block.
+fn build_kind<C: StreamCipher + 'static>(
+    key: [u8; 16],
+    input_file: &str,
+    offset: u64,
+) -> Result<(Kind, Box<dyn BufReadSeek>), Box<dyn std::error::Error>> {
+    let probe_compressor = CustomCompressor::<C>::new_static(key, SQUASHFS_METADATA_SIZE);
+    let probe_kind = Kind::new(probe_compressor);
+
+    let superblock_region =
+        transformer::resolve_superblock_region::<C>(&mut File::open(input_file)?, key, offset)?;
+    let mut probe_reader: Box<dyn BufReadSeek> = Box::new(BufReader::new(
+        HeaderDecryptingReader::new(File::open(input_file)?, vec![superblock_region.clone()]),
+    ));
+    probe_reader.seek(SeekFrom::Start(offset))?;
+    let (superblock, _) =
+        Squashfs::superblock_and_compression_options(&mut probe_reader, &probe_kind)?;
+
+    let compressor = CustomCompressor::<C>::new_static(key, superblock.block_size);
+    let kind = Kind::new(compressor);
+
+    let mut regions = transformer::resolve_table_regions::<C>(
+        &mut File::open(input_file)?,
+        key,
+        offset,
+        superblock.frag_table as u64,
+        superblock.export_table as u64,
+        superblock.id_table as u64,
+    )?;
+    regions.push(superblock_region);
+
+    let reader: Box<dyn BufReadSeek> =
+        Box::new(BufReader::new(HeaderDecryptingReader::new(File::open(input_file)?, regions)));
+
+    Ok((kind, reader))
+}
+
+/// Resolve the decryption key: either the single hex key from --key, or the first candidate
+/// from --keyfile that passes the known-plaintext check against the image's superblock.
+fn resolve_key(
+    cli: &Cli,
+    input_file: &str,
+    offset: u64,
+) -> Result<[u8; 16], Box<dyn std::error::Error>> {
+    if let Some(keyfile) = &cli.keyfile {
+        let contents = std::fs::read_to_string(keyfile)?;
+        for candidate in contents.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            let Ok(key) = hex_to_bytes(candidate) else { continue };
+            if verify_for_cipher(cli.cipher, key, input_file, offset)?.is_valid() {
+                println!("Key validated from wordlist: {}", candidate);
+                return Ok(key);
+            }
+        }
+        return Err(format!("no key in '{}' validated against the image", keyfile.display()).into());
+    }
+
+    let key_str = cli.key.as_deref().ok_or("either --key or --keyfile must be provided")?;
+    let key = hex_to_bytes(key_str).map_err(|e| format!("error parsing key: {e}"))?;
+    println!("Using key: {}", key_str);
+    Ok(key)
+}
+
+/// Run the known-plaintext superblock check with whichever cipher `--cipher` selected.
+fn verify_for_cipher(
+    cipher: Cipher,
+    key: [u8; 16],
+    input_file: &str,
+    offset: u64,
+) -> Result<VerifyResult, Box<dyn std::error::Error>> {
+    match cipher {
+        Cipher::Rc4 => verify::verify_key::<RC4>(key, input_file, offset),
+        Cipher::Aes128Ctr => verify::verify_key::<Aes128Ctr>(key, input_file, offset),
+    }
+}
+
+fn print_verification(result: &VerifyResult) {
+    println!(
+        "Verify: magic {}, block_size {}, bytes_used {}",
+        ok_str(result.magic_ok),
+        ok_str(result.block_size_ok),
+        ok_str(result.bytes_used_ok)
+    );
+}
+
+fn ok_str(ok: bool) -> &'static str {
+    if ok { "ok" } else { "FAILED" }
+}
+
+/// Parse the RKAF container at `input_file`, verify its trailing CRC-32, and return the byte
+/// offset of the named partition's data. Fails loudly on a checksum mismatch rather than
+/// silently proceeding against a possibly-corrupt image.
+fn find_partition_offset(
+    input_file: &str,
+    partition_name: &str,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut file = File::open(input_file)?;
+    rkaf::verify_crc32(&mut file)?;
+
+    file.seek(SeekFrom::Start(0))?;
+    let container = RkafContainer::parse(&mut file)?;
+    println!("Firmware '{}' for chip '{}'", container.firmware, container.chip);
+
+    let partition = container
+        .partition(partition_name)
+        .ok_or_else(|| format!("partition '{}' not found in container", partition_name))?;
+
+    println!(
+        "Using partition '{}' at offset {} ({} bytes)",
+        partition.name, partition.offset, partition.size
+    );
+
+    Ok(partition.offset)
+}
+
+/// Re-encrypt the filesystem and write it back out as a new image. `CustomCompressor`
+/// compresses and encrypts each block as the writer produces them, but the superblock and the
+/// scattered fragment/export/ID table words it leaves behind are still plaintext at that point:
+/// `backhand` only fixes up their real offsets after writing the data/inode/dir tables, so we
+/// can't know them up front the way we can on the read path. Re-open the freshly written image,
+/// parse its (still plaintext) superblock to find them, then encrypt each in place - the same
+/// transform the read path runs in reverse - so the result is a byte-compatible encrypted
+/// SquashFS the original loader accepts.
+fn repack_image<C: StreamCipher + 'static>(
+    filesystem_reader: &FilesystemReader,
+    key: [u8; 16],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut filesystem_writer = FilesystemWriter::from_fs_reader(filesystem_reader)?;
+
+    // Opened for reading as well: the header and table words are read back from it below.
+    let mut output = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open("encrypted.squashfs")?;
+    filesystem_writer.write(&mut output)?;
+
+    let default_kind = Kind::from_target("le_v4_0")?;
+    let mut plain_reader: Box<dyn BufReadSeek> =
+        Box::new(BufReader::new(File::open("encrypted.squashfs")?));
+    let (superblock, _) =
+        Squashfs::superblock_and_compression_options(&mut plain_reader, &default_kind)?;
+    drop(plain_reader);
+
+    let superblock_region = transformer::resolve_superblock_region::<C>(&mut output, key, 0)?;
+    let table_regions = transformer::resolve_table_regions::<C>(
+        &mut output,
+        key,
+        0,
+        superblock.frag_table as u64,
+        superblock.export_table as u64,
+        superblock.id_table as u64,
+    )?;
+
+    for region in std::iter::once(superblock_region).chain(table_regions) {
+        output.seek(SeekFrom::Start(region.start))?;
+        output.write_all(&region.bytes)?;
+    }
+
+    println!("Wrote re-encrypted image to 'encrypted.squashfs'");
+
+    Ok(())
+}
+
+/// Extract a single file from the image to disk, decompressing only the blocks that file
+/// occupies rather than rewriting the entire filesystem.
+fn extract_file(
+    filesystem_reader: &FilesystemReader,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let node = filesystem_reader
+        .files()
+        .find(|node| node.fullpath == path)
+        .ok_or_else(|| format!("File '{}' not found in image", path.display()))?;
+
+    let InnerNode::File(file) = &node.inner else {
+        return Err(format!("'{}' is not a regular file", path.display()).into());
+    };
+
+    let output_name = path
+        .file_name()
+        .ok_or_else(|| format!("'{}' has no file name", path.display()))?;
+    let mut reader = filesystem_reader.file(file).reader();
+    let mut output = BufWriter::new(File::create(output_name)?);
+    std::io::copy(&mut reader, &mut output)?;
+
+    println!("Extracted '{}' to '{}'", path.display(), output_name.to_string_lossy());
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use crate::cipher::StreamCipher;
+
 #[derive(Copy, Clone)]
 pub struct RC4 {
     state: [u8; 256],
@@ -65,6 +67,16 @@ impl RC4 {
     }
 }
 
+impl StreamCipher for RC4 {
+    fn new(key: &[u8]) -> Self {
+        RC4::new(key)
+    }
+
+    fn process(&mut self, data: &mut [u8]) {
+        RC4::process(self, data)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,187 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::crc32::Crc32;
+
+/// Magic bytes at the start of an RKAF (Rockchip) update.img container.
+const RKAF_MAGIC: [u8; 4] = *b"RKAF";
+
+/// Fixed-width, null-padded string fields in the header.
+const NAME_LEN: usize = 32;
+
+/// Size of a little-endian `u32` header field, and of the trailing CRC-32.
+const U32_LEN: usize = 4;
+
+/// A single partition entry from the RKAF partition table: a name plus where to find its data
+/// in the container file.
+#[derive(Debug, Clone)]
+pub struct Partition {
+    pub name: String,
+    /// Byte offset of the partition's data within the container file.
+    pub offset: u64,
+    /// Stored size of the partition's data, in bytes.
+    pub size: u64,
+}
+
+/// Parsed RKAF container header: identifies the firmware/chip the image targets and lists the
+/// partitions packed inside it.
+#[derive(Debug, Clone)]
+pub struct RkafContainer {
+    pub firmware: String,
+    pub chip: String,
+    pub partitions: Vec<Partition>,
+}
+
+impl RkafContainer {
+    /// Parse the RKAF header and partition table from the start of `reader`.
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, String> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic).map_err(|e| format!("failed to read magic: {e}"))?;
+        if magic != RKAF_MAGIC {
+            return Err(format!(
+                "not an RKAF container: expected magic {:?}, found {:?}",
+                RKAF_MAGIC, magic
+            ));
+        }
+
+        let firmware = read_fixed_string(reader, NAME_LEN)?;
+        let chip = read_fixed_string(reader, NAME_LEN)?;
+
+        let partition_count = read_u32(reader)? as usize;
+        let mut partitions = Vec::with_capacity(partition_count);
+        for _ in 0..partition_count {
+            let name = read_fixed_string(reader, NAME_LEN)?;
+            let offset = read_u32(reader)? as u64;
+            let size = read_u32(reader)? as u64;
+            partitions.push(Partition { name, offset, size });
+        }
+
+        Ok(Self { firmware, chip, partitions })
+    }
+
+    /// Find a partition by name, if the container has one.
+    pub fn partition(&self, name: &str) -> Option<&Partition> {
+        self.partitions.iter().find(|p| p.name == name)
+    }
+}
+
+fn read_fixed_string<R: Read>(reader: &mut R, len: usize) -> Result<String, String> {
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf).map_err(|e| format!("failed to read string field: {e}"))?;
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(len);
+    Ok(String::from_utf8_lossy(&buf[..end]).into_owned())
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, String> {
+    let mut buf = [0u8; U32_LEN];
+    reader.read_exact(&mut buf).map_err(|e| format!("failed to read integer field: {e}"))?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Verify the trailing little-endian CRC-32 appended to an RKAF container: the checksum covers
+/// every byte of the file except its own trailing 4 bytes. Streams the file in fixed-size
+/// chunks rather than reading it into memory, since containers can be hundreds of megabytes.
+pub fn verify_crc32<R: Read + Seek>(reader: &mut R) -> Result<(), String> {
+    let total_len = reader.seek(SeekFrom::End(0)).map_err(|e| format!("failed to seek: {e}"))?;
+    if total_len < U32_LEN as u64 {
+        return Err("container is too small to contain a trailing CRC-32".to_string());
+    }
+    let payload_len = total_len - U32_LEN as u64;
+
+    reader.seek(SeekFrom::Start(0)).map_err(|e| format!("failed to seek: {e}"))?;
+    let mut crc = Crc32::new();
+    let mut buffer = [0u8; 64 * 1024];
+    let mut remaining = payload_len;
+    while remaining > 0 {
+        let chunk_len = remaining.min(buffer.len() as u64) as usize;
+        reader
+            .read_exact(&mut buffer[..chunk_len])
+            .map_err(|e| format!("failed to read container: {e}"))?;
+        crc.update(&buffer[..chunk_len]);
+        remaining -= chunk_len as u64;
+    }
+
+    let expected = read_u32(reader)?;
+    let actual = crc.finalize();
+    if actual != expected {
+        return Err(format!(
+            "CRC-32 mismatch: container trailer says {expected:#010x}, computed {actual:#010x}"
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn sample_container() -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&RKAF_MAGIC);
+        data.extend_from_slice(&pad(b"test-firmware", NAME_LEN));
+        data.extend_from_slice(&pad(b"rk3288", NAME_LEN));
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&pad(b"system", NAME_LEN));
+        data.extend_from_slice(&100u32.to_le_bytes());
+        data.extend_from_slice(&200u32.to_le_bytes());
+        data
+    }
+
+    fn pad(name: &[u8], len: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; len];
+        buf[..name.len()].copy_from_slice(name);
+        buf
+    }
+
+    #[test]
+    fn test_parse_header_and_partitions() {
+        let data = sample_container();
+        let container = RkafContainer::parse(&mut Cursor::new(data)).unwrap();
+
+        assert_eq!(container.firmware, "test-firmware");
+        assert_eq!(container.chip, "rk3288");
+        assert_eq!(container.partitions.len(), 1);
+
+        let system = container.partition("system").unwrap();
+        assert_eq!(system.offset, 100);
+        assert_eq!(system.size, 200);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_magic() {
+        let mut data = sample_container();
+        data[0] = b'X';
+        assert!(RkafContainer::parse(&mut Cursor::new(data)).is_err());
+    }
+
+    #[test]
+    fn test_partition_lookup_missing() {
+        let data = sample_container();
+        let container = RkafContainer::parse(&mut Cursor::new(data)).unwrap();
+        assert!(container.partition("boot").is_none());
+    }
+
+    #[test]
+    fn test_verify_crc32_roundtrip() {
+        let mut data = sample_container();
+        let mut crc = Crc32::new();
+        crc.update(&data);
+        data.extend_from_slice(&crc.finalize().to_le_bytes());
+
+        verify_crc32(&mut Cursor::new(data)).unwrap();
+    }
+
+    #[test]
+    fn test_verify_crc32_detects_corruption() {
+        let mut data = sample_container();
+        let mut crc = Crc32::new();
+        crc.update(&data);
+        data.extend_from_slice(&crc.finalize().to_le_bytes());
+
+        // Flip a byte in the payload so it no longer matches the trailing checksum.
+        data[5] ^= 0xff;
+
+        assert!(verify_crc32(&mut Cursor::new(data)).is_err());
+    }
+}
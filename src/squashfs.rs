@@ -1,6 +1,10 @@
 /// Metadata blocks in SquashFS are 8192 bytes in size.
 pub const SQUASHFS_METADATA_SIZE: u32 = 8192;
 
+/// Size of the SquashFS superblock: a fixed 96-byte struct the firmware encrypts directly
+/// rather than through SquashFS block compression.
+pub const SUPERBLOCK_LEN: u64 = 96;
+
 /// Extract the offset within a block from a squashfs inode number
 ///
 /// Squashfs inodes consist of a compressed block number and an
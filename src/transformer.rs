@@ -1,29 +1,241 @@
-use backhand::{BackhandError, transformation::TransformAction};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
 
-use crate::rc4::RC4;
+use crate::cipher::StreamCipher;
+use crate::squashfs::SUPERBLOCK_LEN;
 
-#[derive(Copy, Clone)]
-pub struct CustomTransformer {
-    key: [u8; 16],
+/// A short span of absolute file bytes whose plaintext has already been worked out ahead of
+/// time, to be spliced into the stream in place of whatever cipher-text currently sits there.
+#[derive(Clone)]
+pub struct PlaintextRegion {
+    pub start: u64,
+    pub bytes: Vec<u8>,
 }
 
-impl CustomTransformer {
-    // Transformers need a static lifetime, so we need to leak the box
-    pub fn new_static(key: [u8; 16]) -> &'static Self {
-        let transformer = Box::new(Self { key });
-        Box::leak(transformer)
+impl PlaintextRegion {
+    fn end(&self) -> u64 {
+        self.start + self.bytes.len() as u64
     }
 }
 
-impl TransformAction for CustomTransformer {
-    fn from(&self, buffer: &mut [u8], skip: Option<usize>) -> Result<(), BackhandError> {
-        let mut rc4 = RC4::new(&self.key);
+/// Wraps a reader over an encrypted image and splices in `regions` - the superblock and the
+/// scattered fragment/export/ID table words resolved by [`resolve_table_regions`] - as bytes
+/// pass through. Everything else is left as cipher-text: the data and metadata blocks are
+/// decrypted per block by `CustomCompressor` instead, so overlaying them here too would XOR
+/// them twice.
+pub struct HeaderDecryptingReader<R> {
+    inner: R,
+    regions: Vec<PlaintextRegion>,
+    pos: u64,
+}
+
+impl<R> HeaderDecryptingReader<R> {
+    pub fn new(inner: R, regions: Vec<PlaintextRegion>) -> Self {
+        Self { inner, regions, pos: 0 }
+    }
+
+    fn overlay_regions(&self, buf: &mut [u8], start: u64) {
+        let end = start + buf.len() as u64;
+        for region in &self.regions {
+            if region.start >= end || region.end() <= start {
+                continue;
+            }
 
-        if let Some(skip) = skip {
-            rc4.process(&mut vec![0; skip]);
+            let clip_start = region.start.max(start);
+            let clip_end = region.end().min(end);
+            let buf_range = (clip_start - start) as usize..(clip_end - start) as usize;
+            let region_range = (clip_start - region.start) as usize..(clip_end - region.start) as usize;
+            buf[buf_range].copy_from_slice(&region.bytes[region_range]);
         }
+    }
+}
+
+impl<R: Read> Read for HeaderDecryptingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = self.pos;
+        let n = self.inner.read(buf)?;
+        self.overlay_regions(&mut buf[..n], start);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for HeaderDecryptingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.pos = self.inner.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+fn read_exact_at(file: &mut File, pos: u64, len: usize) -> io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(pos))?;
+    let mut buf = vec![0u8; len];
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Transform the 96-byte superblock at `offset` in place with a fresh keystream. Works for
+/// either direction: called with the original cipher-text it decrypts, called with the writer's
+/// freshly-serialized plaintext it encrypts, since XOR is its own inverse.
+pub fn resolve_superblock_region<C: StreamCipher>(
+    file: &mut File,
+    key: [u8; 16],
+    offset: u64,
+) -> io::Result<PlaintextRegion> {
+    let mut bytes = read_exact_at(file, offset, SUPERBLOCK_LEN as usize)?;
+    C::new(&key).process(&mut bytes);
+    Ok(PlaintextRegion { start: offset, bytes })
+}
+
+/// Resolve (and transform) the scattered fragment/export/ID table words that `backhand`'s
+/// writer places near the end of the file, after the data/inode/dir tables - at offsets only
+/// known once the superblock has been parsed. `frag_table`, `export_table`, and `id_table` are
+/// the corresponding fields read off the (already-decrypted) superblock.
+///
+/// Like the baseline tool this replaces, each table gets its own fresh keystream: the lookup
+/// pointer stored at the superblock-derived offset is transformed first, then - without
+/// reseeding, continuing that same keystream - the size word the pointer leads to. The ID table
+/// only needs its lookup pointer transformed.
+pub fn resolve_table_regions<C: StreamCipher>(
+    file: &mut File,
+    key: [u8; 16],
+    offset: u64,
+    frag_table: u64,
+    export_table: u64,
+    id_table: u64,
+) -> io::Result<Vec<PlaintextRegion>> {
+    let mut regions = resolve_table_pointer::<C>(file, key, offset, frag_table)?;
+    regions.extend(resolve_table_pointer::<C>(file, key, offset, export_table)?);
+
+    let id_ptr_start = offset + id_table;
+    let mut id_ptr_bytes = read_exact_at(file, id_ptr_start, 8)?;
+    C::new(&key).process(&mut id_ptr_bytes);
+    regions.push(PlaintextRegion { start: id_ptr_start, bytes: id_ptr_bytes });
+
+    Ok(regions)
+}
+
+/// Transform the 8-byte table lookup pointer at `offset + lookup_field`, then follow it to
+/// transform the 2-byte size word at the real table start - both with the same cipher instance,
+/// so the size word continues the lookup pointer's keystream rather than restarting it.
+fn resolve_table_pointer<C: StreamCipher>(
+    file: &mut File,
+    key: [u8; 16],
+    offset: u64,
+    lookup_field: u64,
+) -> io::Result<Vec<PlaintextRegion>> {
+    let lookup_start = offset + lookup_field;
+    let mut ptr_bytes = read_exact_at(file, lookup_start, 8)?;
+    let mut cipher = C::new(&key);
+    cipher.process(&mut ptr_bytes);
+
+    let table_ptr = u64::from_le_bytes(ptr_bytes.as_slice().try_into().unwrap());
+    let size_start = offset + table_ptr;
+    let mut size_bytes = read_exact_at(file, size_start, 2)?;
+    cipher.process(&mut size_bytes);
+
+    Ok(vec![
+        PlaintextRegion { start: lookup_start, bytes: ptr_bytes },
+        PlaintextRegion { start: size_start, bytes: size_bytes },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rc4::RC4;
+    use std::io::Write;
+
+    fn write_temp_file(bytes: &[u8]) -> std::path::PathBuf {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir()
+            .join(format!("rkaf-transformer-test-{}-{}.bin", std::process::id(), id));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_overlay_regions_splices_across_read_boundaries() {
+        let regions = vec![
+            PlaintextRegion { start: 2, bytes: vec![0xAA, 0xBB] },
+            PlaintextRegion { start: 6, bytes: vec![0xCC, 0xDD, 0xEE] },
+        ];
+        let reader = HeaderDecryptingReader::new(std::io::Cursor::new(vec![0u8; 0]), regions);
+
+        // A read starting just before the first region and ending mid-way through the second
+        // should overlay both, leaving the untouched bytes around and between them alone.
+        let mut buf = [0xFFu8; 7];
+        reader.overlay_regions(&mut buf, 1);
+        assert_eq!(buf, [0xFF, 0xAA, 0xBB, 0xFF, 0xFF, 0xCC, 0xDD]);
+    }
+
+    #[test]
+    fn test_header_decrypting_reader_decrypts_only_overlaid_regions() {
+        let key = [0x11u8; 16];
+        let total_len = SUPERBLOCK_LEN as usize + 16;
+        let mut plaintext = vec![0u8; total_len];
+        plaintext[0..4].copy_from_slice(b"hsqs");
+        plaintext[total_len - 4..].copy_from_slice(&[1, 2, 3, 4]);
+
+        let mut ciphertext = plaintext.clone();
+        RC4::new(&key).process(&mut ciphertext);
+        let path = write_temp_file(&ciphertext);
+
+        let region =
+            resolve_superblock_region::<RC4>(&mut File::open(&path).unwrap(), key, 0).unwrap();
+        assert_eq!(region.bytes, plaintext[0..SUPERBLOCK_LEN as usize]);
+
+        let mut reader = HeaderDecryptingReader::new(File::open(&path).unwrap(), vec![region]);
+        let mut out = vec![0u8; total_len];
+        reader.read_exact(&mut out).unwrap();
+
+        // Bytes inside the overlaid superblock region come back as plaintext...
+        let sb_len = SUPERBLOCK_LEN as usize;
+        assert_eq!(&out[0..sb_len], &plaintext[0..sb_len]);
+        // ...while anything past it is left as whatever the inner reader produced - still
+        // cipher-text, since nothing overlays that range.
+        assert_eq!(&out[sb_len..], &ciphertext[sb_len..]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_table_pointer_continues_same_keystream() {
+        let key = [0x22u8; 16];
+        let offset = 0u64;
+        let lookup_field = 8u64;
+        let table_ptr: u64 = 40;
+
+        let mut plaintext = vec![0u8; 64];
+        plaintext[lookup_field as usize..lookup_field as usize + 8]
+            .copy_from_slice(&table_ptr.to_le_bytes());
+        plaintext[table_ptr as usize..table_ptr as usize + 2].copy_from_slice(&[0x12, 0x34]);
+
+        // Encrypt the way the image does: a fresh keystream starting at the lookup pointer,
+        // carried on into the size word it points to.
+        let mut ciphertext = plaintext.clone();
+        let mut cipher = RC4::new(&key);
+        cipher.process(&mut ciphertext[lookup_field as usize..lookup_field as usize + 8]);
+        cipher.process(&mut ciphertext[table_ptr as usize..table_ptr as usize + 2]);
+        let path = write_temp_file(&ciphertext);
+
+        let regions = resolve_table_pointer::<RC4>(
+            &mut File::open(&path).unwrap(),
+            key,
+            offset,
+            lookup_field,
+        )
+        .unwrap();
+
+        assert_eq!(regions[0].start, offset + lookup_field);
+        assert_eq!(regions[0].bytes, table_ptr.to_le_bytes());
+        assert_eq!(regions[1].start, offset + table_ptr);
+        assert_eq!(regions[1].bytes, vec![0x12, 0x34]);
 
-        rc4.process(buffer);
-        Ok(())
+        std::fs::remove_file(path).unwrap();
     }
 }
@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::cipher::StreamCipher;
+
+/// Little-endian SquashFS magic ("hsqs") at the start of every superblock.
+const SQUASHFS_MAGIC: u32 = 0x7371_7368;
+
+const MIN_BLOCK_SIZE: u32 = 4 * 1024;
+const MAX_BLOCK_SIZE: u32 = 1024 * 1024;
+
+/// Enough of the raw superblock to read the magic, block_size, and bytes_used fields: the
+/// SquashFS superblock lays out `magic, inode_count, mod_time, block_size, frag_count,
+/// compression_id, block_log, flags, id_count, version_major, version_minor, root_inode,
+/// bytes_used, ...`, so 48 bytes covers everything up to and including `bytes_used`.
+const SUPERBLOCK_PREFIX_LEN: usize = 48;
+
+/// Result of checking a candidate key against the known-plaintext fields of a SquashFS
+/// superblock. Cheaper and faster to fail than letting `backhand` run its full parse on
+/// garbage produced by a wrong key.
+#[derive(Debug)]
+pub struct VerifyResult {
+    pub magic_ok: bool,
+    pub block_size_ok: bool,
+    pub bytes_used_ok: bool,
+}
+
+impl VerifyResult {
+    pub fn is_valid(&self) -> bool {
+        self.magic_ok && self.block_size_ok && self.bytes_used_ok
+    }
+}
+
+/// Decrypt the start of the SquashFS superblock at `offset` in `input_file` with `key` and
+/// check it against what a valid image must contain: the "hsqs" magic, a power-of-two block
+/// size between 4 KiB and 1 MiB, and a `bytes_used` that fits within the remaining file.
+pub fn verify_key<C: StreamCipher>(
+    key: [u8; 16],
+    input_file: &str,
+    offset: u64,
+) -> Result<VerifyResult, Box<dyn std::error::Error>> {
+    let mut file = File::open(input_file)?;
+    let file_len = file.seek(SeekFrom::End(0))?;
+
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buffer = [0u8; SUPERBLOCK_PREFIX_LEN];
+    file.read_exact(&mut buffer)?;
+
+    let mut cipher = C::new(&key);
+    cipher.process(&mut buffer);
+
+    let magic = u32::from_le_bytes(buffer[0..4].try_into().unwrap());
+    let block_size = u32::from_le_bytes(buffer[12..16].try_into().unwrap());
+    let bytes_used = u64::from_le_bytes(buffer[40..48].try_into().unwrap());
+
+    Ok(VerifyResult {
+        magic_ok: magic == SQUASHFS_MAGIC,
+        block_size_ok: block_size.is_power_of_two()
+            && (MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&block_size),
+        bytes_used_ok: bytes_used <= file_len.saturating_sub(offset),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rc4::RC4;
+    use std::io::Write;
+
+    fn encrypted_superblock(key: &[u8; 16], block_size: u32, bytes_used: u64) -> Vec<u8> {
+        let mut buffer = vec![0u8; SUPERBLOCK_PREFIX_LEN];
+        buffer[0..4].copy_from_slice(&SQUASHFS_MAGIC.to_le_bytes());
+        buffer[12..16].copy_from_slice(&block_size.to_le_bytes());
+        buffer[40..48].copy_from_slice(&bytes_used.to_le_bytes());
+
+        let mut cipher = RC4::new(key);
+        cipher.process(&mut buffer);
+        buffer
+    }
+
+    fn write_temp_file(bytes: &[u8]) -> std::path::PathBuf {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir()
+            .join(format!("rkaf-verify-test-{}-{}.bin", std::process::id(), id));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_verify_key_accepts_correct_key() {
+        let key = [0x11u8; 16];
+        let mut data = encrypted_superblock(&key, 131_072, 1024);
+        data.resize(1024, 0);
+        let path = write_temp_file(&data);
+
+        let result = verify_key::<RC4>(key, path.to_str().unwrap(), 0).unwrap();
+        assert!(result.is_valid());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_verify_key_rejects_wrong_key() {
+        let key = [0x11u8; 16];
+        let wrong_key = [0x22u8; 16];
+        let data = encrypted_superblock(&key, 131_072, 1024);
+        let path = write_temp_file(&data);
+
+        let result = verify_key::<RC4>(wrong_key, path.to_str().unwrap(), 0).unwrap();
+        assert!(!result.is_valid());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_verify_key_rejects_bad_block_size() {
+        let key = [0x11u8; 16];
+        // Not a power of two, and outside the 4 KiB - 1 MiB range.
+        let data = encrypted_superblock(&key, 3000, 1024);
+        let path = write_temp_file(&data);
+
+        let result = verify_key::<RC4>(key, path.to_str().unwrap(), 0).unwrap();
+        assert!(result.magic_ok);
+        assert!(!result.block_size_ok);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_verify_key_rejects_bytes_used_past_eof() {
+        let key = [0x11u8; 16];
+        let data = encrypted_superblock(&key, 131_072, 10_000_000);
+        let path = write_temp_file(&data);
+
+        let result = verify_key::<RC4>(key, path.to_str().unwrap(), 0).unwrap();
+        assert!(result.magic_ok);
+        assert!(!result.bytes_used_ok);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}